@@ -0,0 +1,38 @@
+//! Runtime support shared by the `shapely` family of derive macros.
+//!
+//! This crate is intentionally tiny: the derive macros in `shapely-derive`
+//! do the heavy lifting at compile time and only rely on the single runtime
+//! helper defined here.
+
+#![feature(generators)]
+#![feature(generator_trait)]
+
+use std::ops::Generator;
+use std::ops::GeneratorState;
+use std::pin::Pin;
+
+pub use shapely_derive::Iterator;
+
+
+
+// ===================================
+// === GeneratorIteratorAdapter ===
+// ===================================
+
+/// Adapts a non-returning `Generator` into an `Iterator`, yielding whatever
+/// the generator `yield`s. Derived `Iterator` impls are generated as
+/// generator bodies (rather than hand-written state machines), so every one
+/// of them is wrapped in this adapter to satisfy `Iterator`.
+#[derive(Clone,Copy,Debug)]
+pub struct GeneratorIteratorAdapter<G>(pub G);
+
+impl<G> Iterator for GeneratorIteratorAdapter<G>
+where G: Generator<Return=()> + Unpin {
+    type Item = G::Yield;
+    fn next(&mut self) -> Option<Self::Item> {
+        match Pin::new(&mut self.0).resume(()) {
+            GeneratorState::Yielded(item) => Some(item),
+            GeneratorState::Complete(())  => None,
+        }
+    }
+}
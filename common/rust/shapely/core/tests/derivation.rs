@@ -1,6 +1,8 @@
 #![feature(generators)]
 #![feature(type_alias_impl_trait)]
 
+use std::marker::PhantomData;
+
 use shapely::*;
 
 // =============
@@ -54,7 +56,20 @@ fn derive_iterator_single_t() {
     for i in &pair {
         sum += i;
     }
-    assert_eq!(sum, pair.0 + pair.1)
+    assert_eq!(sum, pair.0 + pair.1);
+
+    // IntoIterator for Val (owned)
+    is_into_iterator::<PairTT<i32>>();
+    let pair = get_pair();
+    assert_eq!(to_vector(pair.into_iter()), vec![4, 49]);
+
+    // consuming for loop, using the by-value `IntoIterator`
+    let pair = get_pair();
+    let mut sum = 0;
+    for i in pair {
+        sum += i;
+    }
+    assert_eq!(sum, 4 + 49)
 }
 
 // ===================================
@@ -71,6 +86,114 @@ fn two_params() {
     assert_eq!(to_vector(pair.iter().copied()), vec![10]);
 }
 
+#[derive(Iterator, Eq, PartialEq, Debug)]
+#[iterator(param = "U")]
+pub struct PairUVDrivenByU<U,V>(U,V);
+
+#[test]
+fn configurable_driving_param() {
+    // `#[iterator(param = "U")]` overrides the default (last parameter) driver
+    let pair = PairUVDrivenByU(5, 10);
+    assert_eq!(to_vector(pair.iter().copied()), vec![5]);
+}
+
+// =====================
+// === Enum variants ===
+// =====================
+
+#[derive(Iterator, Eq, PartialEq, Debug)]
+pub enum Shape<T> {
+    Circle(T),
+    Rect(T, T),
+    Empty,
+}
+
+#[test]
+fn derive_iterator_enum() {
+    is_into_iterator::<&Shape<i32>>();
+    is_into_iterator::<&mut Shape<i32>>();
+    is_into_iterator::<Shape<i32>>();
+
+    assert_eq!(to_vector(Shape::Circle(4).iter().copied()),    vec![4]);
+    assert_eq!(to_vector(Shape::Rect(4, 5).iter().copied()),   vec![4, 5]);
+    assert_eq!(to_vector(Shape::<i32>::Empty.iter().copied()), Vec::<i32>::new());
+
+    let mut rect = Shape::Rect(4, 5);
+    for i in &mut rect {
+        *i += 1;
+    }
+    assert_eq!(rect, Shape::Rect(5, 6));
+
+    assert_eq!(to_vector(Shape::Rect(4, 5).into_iter()), vec![4, 5]);
+}
+
+// ========================================
+// === Field attributes and PhantomData ===
+// ========================================
+
+#[derive(Iterator, Eq, PartialEq, Debug)]
+pub struct Tagged<T>(T, PhantomData<T>);
+
+#[test]
+fn phantom_data_is_skipped() {
+    let tagged = Tagged(4, PhantomData);
+    assert_eq!(to_vector(tagged.iter().copied()), vec![4]);
+}
+
+#[derive(Iterator, Eq, PartialEq, Debug)]
+pub struct Picky<T>(T, #[iterator(ignore)] T, #[iterator(iterate)] T);
+
+#[test]
+fn field_attributes_override_selection() {
+    let picky = Picky(4, 5, 6);
+    assert_eq!(to_vector(picky.into_iter()), vec![4, 6]);
+}
+
+#[derive(Iterator, Eq, PartialEq, Debug)]
+pub struct StubbornMarker<T>(T, #[iterator(iterate)] PhantomData<T>);
+
+#[test]
+fn iterate_cannot_resurrect_phantom_data() {
+    // `#[iterator(iterate)]` can't force a `PhantomData<T>` field to yield a
+    // `T` it never actually holds — the automatic phantom-data skip wins.
+    let marker = StubbornMarker(7, PhantomData);
+    assert_eq!(to_vector(marker.into_iter()), vec![7]);
+}
+
+// =============================================
+// === Fields referencing the type parameter ===
+// =============================================
+
+#[derive(Iterator, Eq, PartialEq, Debug)]
+pub struct WithRef<'a, T>(T, &'a T);
+
+#[test]
+fn ref_field_contributes_to_iter_only() {
+    let with_ref = WithRef(4, &5);
+    assert_eq!(to_vector(with_ref.iter().copied()), vec![4, 5]);
+    assert_eq!(to_vector(with_ref.into_iter()), vec![4]);
+}
+
+#[derive(Iterator, Eq, PartialEq, Debug)]
+pub struct Nested<T>(T, #[iterator(flatten)] Vec<T>);
+
+#[test]
+fn flattened_container_field() {
+    let get = || Nested(4, vec![5, 6]);
+
+    let nested = get();
+    assert_eq!(to_vector(nested.iter().copied()), vec![4, 5, 6]);
+
+    let mut nested = get();
+    for i in nested.iter_mut() {
+        *i += 1;
+    }
+    assert_eq!(nested, Nested(5, vec![6, 7]));
+
+    let nested = get();
+    assert_eq!(to_vector(nested.into_iter()), vec![4, 5, 6]);
+}
+
 // ======================================
 // === Struct without any type params ===
 // ======================================
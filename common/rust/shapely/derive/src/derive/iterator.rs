@@ -0,0 +1,412 @@
+//! Implementation of the `#[derive(Iterator)]` macro.
+//!
+//! For a struct's or enum's last type parameter `T`, this generates:
+//! - `fn iter(&self) -> impl Iterator<Item = &T>`
+//! - `fn iter_mut(&mut self) -> impl Iterator<Item = &mut T>`
+//! - `fn into_iter(self) -> impl Iterator<Item = T>`
+//! - `IntoIterator` impls for `&Self`, `&mut Self` and `Self`, built on top
+//!   of the three methods above.
+//!
+//! For an enum, each variant's arm yields whichever of its fields hold `T`;
+//! variants with no such field simply yield nothing.
+//!
+//! A field can override whether it participates in iteration with
+//! `#[iterator(ignore)]` or `#[iterator(iterate)]`; `PhantomData<_>` fields
+//! are always skipped automatically.
+//!
+//! Selection is not limited to fields whose type is exactly `T`: a
+//! type-visitor checks whether `T` appears anywhere inside a field's type.
+//! A field of type `&T` contributes the reference directly (to `iter` only,
+//! since it can't yield `&mut T` or owned `T`). A field whose own type
+//! implements `IntoIterator`/`&IntoIterator`/`&mut IntoIterator` over `T`
+//! (e.g. `Vec<T>`) must be marked `#[iterator(flatten)]`, which chains its
+//! own iterator into the generated stream instead of yielding the container
+//! itself.
+//!
+//! Types without type parameters derive a no-op: no methods are generated,
+//! and deriving still compiles cleanly.
+//!
+//! For a multi-parameter type, the driving type parameter defaults to the
+//! last one declared, but a struct/enum-level `#[iterator(param = "U")]`
+//! attribute can pick a different one.
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use quote::format_ident;
+use syn::Data;
+use syn::DeriveInput;
+use syn::Field;
+use syn::Fields;
+use syn::GenericParam;
+use syn::Generics;
+use syn::Ident;
+use syn::Index;
+use syn::Type;
+use syn::Variant;
+use syn::visit::Visit;
+
+
+
+// ==============
+// === Driver ===
+// ==============
+
+/// Entry point invoked by `#[proc_macro_derive(Iterator)]`.
+pub fn derive(decl: &DeriveInput) -> TokenStream {
+    let param = match container_param(decl) {
+        Some(name) => resolve_named_param(&decl.generics, &name, &decl.ident),
+        None => match last_type_param(&decl.generics) {
+            Some(param) => param,
+            // No type parameters: `derive(Iterator)` is a no-op.
+            None => return quote!{},
+        },
+    };
+
+    let body = match &decl.data {
+        Data::Struct(data) => Body::Struct(matching_fields(&data.fields, param)),
+        Data::Enum(data)   => Body::Enum(data.variants.iter()
+            .map(|variant| variant_arms(&decl.ident, variant, param))
+            .collect()),
+        Data::Union(_)     => panic!("#[derive(Iterator)] does not support unions"),
+    };
+
+    generate(decl, param, &body)
+}
+
+/// How a selected field contributes items to the generated iterators.
+enum FieldKind {
+    /// The field's own value/reference is yielded directly.
+    Direct,
+    /// The field is a reference to the driving type parameter (`&T`); it is
+    /// only included in `iter`, since `iter_mut`/`into_iter` cannot produce
+    /// `&mut T`/`T` out of a shared reference.
+    RefOnly,
+    /// The field is a container (e.g. `Vec<T>`); its own `IntoIterator` is
+    /// chained into the generated stream.
+    Flatten,
+}
+
+impl FieldKind {
+    /// Whether a field of this kind is included in a given context (`iter`
+    /// if `in_ref_context`, `iter_mut`/`into_iter` otherwise).
+    fn included_in(&self, in_ref_context: bool) -> bool {
+        match self {
+            FieldKind::RefOnly => in_ref_context,
+            _                  => true,
+        }
+    }
+}
+
+/// The fields (struct) or per-variant match arms (enum) that the generated
+/// `iter`/`iter_mut`/`into_iter` bodies are built from.
+enum Body {
+    Struct(Vec<(TokenStream, FieldKind)>),
+    /// One `(iter, iter_mut, into_iter)` arm per variant.
+    Enum(Vec<(TokenStream, TokenStream, TokenStream)>),
+}
+
+/// Builds the three match arms (`iter`, `iter_mut`, `into_iter`) for one enum
+/// variant: each binds every field (unselected, or excluded from this
+/// context, as `_`), followed by a `yield` for each included field. Fields
+/// are bound identically across the three arms — match ergonomics turn the
+/// bindings into `&T`/`&mut T`/`T` depending on whether `self` is matched by
+/// reference — so only which fields are *included* differs between arms. A
+/// `&T` field (only in the `iter` arm) is dereferenced once when yielded.
+fn variant_arms(enum_ident: &Ident, variant: &Variant, param: &Ident) -> (TokenStream, TokenStream, TokenStream) {
+    let variant_ident = &variant.ident;
+    let bindings: Vec<_> = variant.fields.iter().enumerate()
+        .map(|(i, field)| match &field.ident {
+            Some(ident) => ident.clone(),
+            None        => format_ident!("field{}", i),
+        })
+        .collect();
+    let kinds: Vec<_> = variant.fields.iter().map(|field| field_kind(field, param)).collect();
+
+    let arm = |in_ref_context: bool| {
+        let included = |kind: &Option<FieldKind>| kind.as_ref().map_or(false, |k| k.included_in(in_ref_context));
+        let pattern = bindings.iter().zip(&kinds)
+            .map(|(binding, kind)| if included(kind) { quote!{ #binding } } else { quote!{ _ } });
+        let yields  = bindings.iter().zip(&kinds)
+            .filter(|(_, kind)| included(kind))
+            .map(|(binding, kind)| {
+                let kind = kind.as_ref().unwrap();
+                // A `&T` field's binding is `&&T` under `match self` on `&Self`.
+                let accessor = match kind {
+                    FieldKind::RefOnly => quote!{ *#binding },
+                    _                  => quote!{ #binding },
+                };
+                field_yield(&accessor, kind)
+            });
+        match &variant.fields {
+            Fields::Named(_)   => quote!{ #enum_ident::#variant_ident { #(#pattern),* } => { #(#yields)* } },
+            Fields::Unnamed(_) => quote!{ #enum_ident::#variant_ident ( #(#pattern),* ) => { #(#yields)* } },
+            Fields::Unit       => quote!{ #enum_ident::#variant_ident => {} },
+        }
+    };
+
+    (arm(true), arm(false), arm(false))
+}
+
+/// The statement yielding a selected field's contribution, given the tokens
+/// used to access it (`self.0` for a struct field, or its match binding for
+/// an enum field).
+fn field_yield(accessor: &TokenStream, kind: &FieldKind) -> TokenStream {
+    match kind {
+        FieldKind::Direct | FieldKind::RefOnly => quote! { yield #accessor; },
+        FieldKind::Flatten                     => quote! { for item in #accessor { yield item; } },
+    }
+}
+
+/// The identifier of the type's last type parameter, if it has any.
+fn last_type_param(generics: &Generics) -> Option<&Ident> {
+    generics.params.iter().rev().find_map(|param| match param {
+        GenericParam::Type(type_param) => Some(&type_param.ident),
+        _ => None,
+    })
+}
+
+/// The name given by a struct/enum-level `#[iterator(param = "...")]`
+/// attribute, if present, overriding the default (last type parameter).
+fn container_param(decl: &DeriveInput) -> Option<String> {
+    let attr = decl.attrs.iter().find(|attr| attr.path.is_ident("iterator"))?;
+    let meta = attr.parse_args::<syn::MetaNameValue>()
+        .expect("expected `#[iterator(param = \"...\")]`");
+    if !meta.path.is_ident("param") {
+        panic!("unknown #[iterator(...)] attribute, expected `param = \"...\"`");
+    }
+    match meta.lit {
+        syn::Lit::Str(name) => Some(name.value()),
+        _ => panic!("#[iterator(param = ...)] expects a string literal"),
+    }
+}
+
+/// Resolves a `#[iterator(param = "...")]` name against the type's generics,
+/// with a clear compile error if it does not name a type parameter.
+fn resolve_named_param<'g>(generics: &'g Generics, name: &str, type_ident: &Ident) -> &'g Ident {
+    generics.type_params().map(|param| &param.ident).find(|ident| *ident == name)
+        .unwrap_or_else(|| panic!(
+            "#[iterator(param = \"{}\")] does not name a type parameter of `{}`", name, type_ident
+        ))
+}
+
+/// The fields that should be iterated over, paired with the tokens used to
+/// access them off of `self` (`self.0`, `self.foo`, ...) and how. See
+/// `field_kind` for the selection rules.
+fn matching_fields<'f>(fields: &'f Fields, param: &Ident) -> Vec<(TokenStream, FieldKind)> {
+    fields.iter().enumerate()
+        .filter_map(|(i, field)| field_kind(field, param).map(|kind| (field_accessor(field, i), kind)))
+        .collect()
+}
+
+/// Whether and how a field should be iterated over.
+///
+/// `PhantomData<_>` fields never hold a real value of the driving type
+/// parameter, so they are always excluded — not even `#[iterator(iterate)]`
+/// can override this. Otherwise, a `#[iterator(ignore)]` field is always
+/// excluded, and a `#[iterator(iterate)]` or `#[iterator(flatten)]` field is
+/// always included, as `Direct`/`Flatten` respectively, overriding the
+/// default rule. Absent either attribute, a field is included iff the
+/// driving type parameter appears anywhere in its type: exactly (`Direct`),
+/// behind a shared reference (`RefOnly`), or nested in some other way, which
+/// is only supported when explicitly flattened.
+fn field_kind(field: &Field, param: &Ident) -> Option<FieldKind> {
+    if is_phantom_data(&field.ty) {
+        return None;
+    }
+
+    match field_mode(field).as_ref().map(Ident::to_string).as_deref() {
+        Some("ignore")  => return None,
+        Some("iterate") => return Some(FieldKind::Direct),
+        Some("flatten") => return Some(FieldKind::Flatten),
+        Some(other)     => panic!("unknown #[iterator({})] attribute, expected `ignore`, `iterate` or `flatten`", other),
+        None            => {}
+    }
+
+    if is_type_param(&field.ty, param) {
+        Some(FieldKind::Direct)
+    } else if is_ref_of_type_param(&field.ty, param) {
+        Some(FieldKind::RefOnly)
+    } else if is_mut_ref_of_type_param(&field.ty, param) {
+        panic!(
+            "field type is `&mut {0}`: #[derive(Iterator)] only ever hands out one \
+             `&{0}`/`&mut {0}`/`{0}` per field, so it can't also reborrow this one; bind the \
+             `{0}` directly instead, or drop the field from iteration with #[iterator(ignore)]",
+            param
+        );
+    } else if contains_type_param(&field.ty, param) {
+        panic!(
+            "field type mentions the driving type parameter in a way #[derive(Iterator)] \
+             does not handle on its own; if its type implements IntoIterator (or &/&mut \
+             IntoIterator) over {}, annotate it with #[iterator(flatten)]",
+            param
+        );
+    } else {
+        None
+    }
+}
+
+/// The identifier inside this field's `#[iterator(..)]` attribute, if any.
+fn field_mode(field: &Field) -> Option<Ident> {
+    let attr = field.attrs.iter().find(|attr| attr.path.is_ident("iterator"))?;
+    Some(attr.parse_args::<Ident>().expect("expected `#[iterator(<mode>)]`"))
+}
+
+fn is_type_param(ty: &Type, param: &Ident) -> bool {
+    match ty {
+        Type::Path(path) => path.qself.is_none()
+            && path.path.get_ident().map_or(false, |ident| ident == param),
+        _ => false,
+    }
+}
+
+fn is_ref_of_type_param(ty: &Type, param: &Ident) -> bool {
+    match ty {
+        Type::Reference(reference) => reference.mutability.is_none() && is_type_param(&reference.elem, param),
+        _ => false,
+    }
+}
+
+fn is_mut_ref_of_type_param(ty: &Type, param: &Ident) -> bool {
+    match ty {
+        Type::Reference(reference) => reference.mutability.is_some() && is_type_param(&reference.elem, param),
+        _ => false,
+    }
+}
+
+fn is_phantom_data(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => path.qself.is_none()
+            && path.path.segments.last().map_or(false, |seg| seg.ident == "PhantomData"),
+        _ => false,
+    }
+}
+
+/// Whether `param` appears anywhere within `ty`, however deeply nested (e.g.
+/// as `Vec<T>`, `&T`, or `Option<Box<T>>`). Mirrors the `find_type_parameters`
+/// visitor rustc's own derive machinery uses to decide which fields a
+/// generic parameter's bound should apply to.
+fn contains_type_param(ty: &Type, param: &Ident) -> bool {
+    struct Finder<'a> { param: &'a Ident, found: bool }
+    impl<'a, 'ast> Visit<'ast> for Finder<'a> {
+        fn visit_ident(&mut self, ident: &'ast Ident) {
+            self.found = self.found || ident == self.param;
+        }
+    }
+    let mut finder = Finder { param, found: false };
+    finder.visit_type(ty);
+    finder.found
+}
+
+fn field_accessor(field: &Field, index: usize) -> TokenStream {
+    match &field.ident {
+        Some(ident) => quote! { #ident },
+        None        => { let index = Index::from(index); quote! { #index } },
+    }
+}
+
+/// The type's generic parameters, as bare identifiers usable as type
+/// arguments (e.g. `T`, or `'t` for a lifetime).
+fn type_args(generics: &Generics) -> Vec<TokenStream> {
+    generics.params.iter().map(|param| match param {
+        GenericParam::Type(param)     => { let ident = &param.ident;    quote!{ #ident } }
+        GenericParam::Lifetime(param) => { let life  = &param.lifetime; quote!{ #life  } }
+        GenericParam::Const(param)    => { let ident = &param.ident;    quote!{ #ident } }
+    }).collect()
+}
+
+fn generate(decl: &DeriveInput, param: &Ident, body: &Body) -> TokenStream {
+    let ident               = &decl.ident;
+    let generics            = &decl.generics;
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let ty_args             = type_args(generics);
+
+    // Generics augmented with an extra `'t` lifetime, used by the
+    // reference-based `iter`/`iter_mut`/`IntoIterator` impls.
+    let mut ref_generics = generics.clone();
+    ref_generics.params.insert(0, syn::parse_quote!('t));
+    let (ref_impl_generics, _, _) = ref_generics.split_for_impl();
+    let ref_ty_args: Vec<_> = std::iter::once(quote!{'t}).chain(ty_args.iter().cloned()).collect();
+
+    let iter_ref_ty  = format_ident!("{}ShapelyIterRef",  ident);
+    let iter_mut_ty  = format_ident!("{}ShapelyIterMut",  ident);
+    let into_iter_ty = format_ident!("{}ShapelyIntoIter", ident);
+
+    let (ref_body, mut_body, owned_body) = match body {
+        Body::Struct(fields) => {
+            // Unlike an enum's match bindings, `self.field` doesn't benefit
+            // from ergonomics: the `&`/`&mut` prefix (or lack thereof) must
+            // be chosen per field kind and context here.
+            let refs  = fields.iter()
+                .filter(|(_, kind)| kind.included_in(true))
+                .map(|(f, kind)| field_yield(&match kind {
+                    FieldKind::RefOnly => quote!{ self.#f },
+                    _                  => quote!{ &self.#f },
+                }, kind));
+            let muts  = fields.iter()
+                .filter(|(_, kind)| kind.included_in(false))
+                .map(|(f, kind)| field_yield(&quote!{ &mut self.#f }, kind));
+            let owned = fields.iter()
+                .filter(|(_, kind)| kind.included_in(false))
+                .map(|(f, kind)| field_yield(&quote!{ self.#f }, kind));
+            (quote!{ #(#refs)* }, quote!{ #(#muts)* }, quote!{ #(#owned)* })
+        }
+        Body::Enum(arms) => {
+            let ref_arms:   Vec<_> = arms.iter().map(|(r, _, _)| r).collect();
+            let mut_arms:   Vec<_> = arms.iter().map(|(_, m, _)| m).collect();
+            let owned_arms: Vec<_> = arms.iter().map(|(_, _, o)| o).collect();
+            (
+                quote!{ match self { #(#ref_arms)*   } },
+                quote!{ match self { #(#mut_arms)*   } },
+                quote!{ match self { #(#owned_arms)* } },
+            )
+        }
+    };
+
+    quote! {
+        #[allow(non_camel_case_types)]
+        type #iter_ref_ty #ref_impl_generics = impl Iterator<Item = &'t #param> + 't #where_clause;
+        #[allow(non_camel_case_types)]
+        type #iter_mut_ty #ref_impl_generics = impl Iterator<Item = &'t mut #param> + 't #where_clause;
+        #[allow(non_camel_case_types)]
+        type #into_iter_ty #impl_generics = impl Iterator<Item = #param> #where_clause;
+
+        impl #ref_impl_generics #ident #ty_generics #where_clause {
+            /// Iterates over references to the fields holding the type's
+            /// driving type parameter.
+            pub fn iter(&'t self) -> #iter_ref_ty<#(#ref_ty_args),*> {
+                shapely::GeneratorIteratorAdapter(move || { #ref_body })
+            }
+
+            /// Iterates over mutable references to the fields holding the
+            /// type's driving type parameter.
+            pub fn iter_mut(&'t mut self) -> #iter_mut_ty<#(#ref_ty_args),*> {
+                shapely::GeneratorIteratorAdapter(move || { #mut_body })
+            }
+        }
+
+        impl #impl_generics #ident #ty_generics #where_clause {
+            /// Consumes `self`, yielding owned values in declaration order.
+            pub fn into_iter(self) -> #into_iter_ty<#(#ty_args),*> {
+                shapely::GeneratorIteratorAdapter(move || { #owned_body })
+            }
+        }
+
+        impl #ref_impl_generics IntoIterator for &'t #ident #ty_generics #where_clause {
+            type Item     = &'t #param;
+            type IntoIter = #iter_ref_ty<#(#ref_ty_args),*>;
+            fn into_iter(self) -> Self::IntoIter { self.iter() }
+        }
+
+        impl #ref_impl_generics IntoIterator for &'t mut #ident #ty_generics #where_clause {
+            type Item     = &'t mut #param;
+            type IntoIter = #iter_mut_ty<#(#ref_ty_args),*>;
+            fn into_iter(self) -> Self::IntoIter { self.iter_mut() }
+        }
+
+        impl #impl_generics IntoIterator for #ident #ty_generics #where_clause {
+            type Item     = #param;
+            type IntoIter = #into_iter_ty<#(#ty_args),*>;
+            fn into_iter(self) -> Self::IntoIter { self.into_iter() }
+        }
+    }
+}
@@ -0,0 +1,3 @@
+//! Implementations of the individual derive macros exposed by this crate.
+
+pub mod iterator;
@@ -0,0 +1,24 @@
+//! Proc-macro implementations backing the `shapely` crate's derive macros.
+
+extern crate proc_macro;
+
+mod derive;
+
+use proc_macro::TokenStream;
+use syn::parse_macro_input;
+use syn::DeriveInput;
+
+
+
+// ================
+// === Iterator ===
+// ================
+
+/// Derives `iter`, `iter_mut`, `into_iter` and the corresponding `IntoIterator`
+/// impls (for `&Self`, `&mut Self` and `Self`) over the fields holding the
+/// type's driving type parameter. See `derive::iterator` for details.
+#[proc_macro_derive(Iterator, attributes(iterator))]
+pub fn derive_iterator(input: TokenStream) -> TokenStream {
+    let decl = parse_macro_input!(input as DeriveInput);
+    derive::iterator::derive(&decl).into()
+}